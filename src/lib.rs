@@ -0,0 +1,15 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// The `std`/`alloc` feature split and the `alloc` dependency itself still need to be
+// declared in Cargo.toml (`[features] default = ["std"]`, `std = []`, `alloc` as a
+// required dependency) — that manifest isn't part of this source tree, so the
+// `no_std` build this crate root enables can't actually be exercised until it's
+// added there.
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+pub mod crypto;
+pub mod result;
+pub mod write;
+
+pub use result::{CryptoError, DatabaseIntegrityError, Error, Result};
@@ -1,18 +1,62 @@
-pub type Result<T> = std::result::Result<T, Error>;
+// See the crate root for the `std`/`no_std` switch and `extern crate alloc`.
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// An owned, `Error`-implementing stand-in for `hmac::crypto_mac::InvalidKeyLength`,
+/// captured at conversion time since the upstream type doesn't implement `Error` itself.
+#[derive(Debug)]
+pub struct InvalidKeyLengthError {
+    message: String,
+}
+
+#[cfg_attr(tarpaulin, skip)]
+impl core::fmt::Display for InvalidKeyLengthError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidKeyLengthError {}
+
+/// An owned, `Error`-implementing stand-in for `stream_cipher::InvalidKeyNonceLength`,
+/// captured at conversion time since the upstream type doesn't implement `Error` itself.
+#[derive(Debug)]
+pub struct InvalidKeyNonceLengthError {
+    message: String,
+}
+
+#[cfg_attr(tarpaulin, skip)]
+impl core::fmt::Display for InvalidKeyNonceLengthError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidKeyNonceLengthError {}
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CryptoError {
     Argon2 {
         e: argon2::Error,
     },
     InvalidKeyLength {
-        e: hmac::crypto_mac::InvalidKeyLength,
+        e: InvalidKeyLengthError,
     },
     InvalidKeyIvLength {
         e: block_modes::InvalidKeyIvLength,
     },
     InvalidKeyNonceLength {
-        e: stream_cipher::InvalidKeyNonceLength,
+        e: InvalidKeyNonceLengthError,
     },
     BlockMode {
         e: block_modes::BlockModeError,
@@ -20,6 +64,7 @@ pub enum CryptoError {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DatabaseIntegrityError {
     Compression,
     Crypto {
@@ -53,6 +98,12 @@ pub enum DatabaseIntegrityError {
     InvalidKDFUUID {
         uuid: Vec<u8>,
     },
+    /// A well-formed (16-byte) KDF UUID that isn't one this crate implements
+    /// (AES-KDF, Argon2d, Argon2id). Raised by `crypto::Kdf::from_uuid`; a UUID of
+    /// the wrong length is reported as `InvalidKDFUUID` instead.
+    UnsupportedKDF {
+        uuid: Vec<u8>,
+    },
     MissingKDFParams {
         key: String,
     },
@@ -62,9 +113,22 @@ pub enum DatabaseIntegrityError {
     InvalidOuterCipherID {
         cid: Vec<u8>,
     },
+    /// A well-formed (16-byte) outer cipher ID that isn't one this crate implements
+    /// (AES-256, Twofish, ChaCha20). Raised by `crypto::OuterCipher::from_uuid`; an
+    /// ID of the wrong length is reported as `InvalidOuterCipherID` instead.
+    UnsupportedCipher {
+        cid: Vec<u8>,
+    },
     InvalidInnerCipherID {
         cid: u32,
     },
+    /// An inner stream cipher ID that KeePass defines but this crate doesn't
+    /// implement: `0` (none) or `1` (ARC4Variant). Raised by
+    /// `crypto::InnerCipher::from_id`; any other unimplemented ID is reported as
+    /// `InvalidInnerCipherID`.
+    UnsupportedStreamCipher {
+        cid: u32,
+    },
     InvalidCompressionSuite {
         cid: u32,
     },
@@ -77,25 +141,56 @@ pub enum DatabaseIntegrityError {
     XMLParsing {
         e: xml::reader::Error,
     },
+    XMLWriting {
+        e: xml::writer::Error,
+    },
+    /// Raised by `crypto::block_hmac` (and so by `write::hmac_blocks`) when the
+    /// block's HMAC cannot be keyed with the supplied block key.
+    BlockHashGeneration {
+        block_index: usize,
+    },
     Base64 {
         e: base64::DecodeError,
     },
     UTF8 {
-        e: std::str::Utf8Error,
+        e: core::str::Utf8Error,
     },
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "std")]
     IO { e: std::io::Error },
     DatabaseIntegrity { e: DatabaseIntegrityError },
     IncorrectKey,
     InvalidKeyFile,
 }
 
+impl Error {
+    /// Returns `true` if this error means the supplied password / key file was wrong,
+    /// as opposed to the database itself being unreadable.
+    pub fn is_authentication_failure(&self) -> bool {
+        matches!(self, Error::IncorrectKey | Error::InvalidKeyFile)
+    }
+
+    /// Returns `true` if this error means the database file itself is corrupt,
+    /// malformed, or otherwise fails to parse, as opposed to a wrong password or an
+    /// IO failure. `DatabaseIntegrityError::Crypto` is deliberately excluded: a
+    /// block-mode/decryption failure there is as likely to be the symptom of a wrong
+    /// key as of a broken file, so it doesn't belong confidently on either side of
+    /// this split.
+    pub fn is_corruption(&self) -> bool {
+        match self {
+            Error::DatabaseIntegrity { e } => !matches!(e, DatabaseIntegrityError::Crypto { .. }),
+            _ => false,
+        }
+    }
+}
+
 #[cfg_attr(tarpaulin, skip)]
-impl std::fmt::Display for DatabaseIntegrityError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for DatabaseIntegrityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
             "Database integrity error: {}",
@@ -145,12 +240,27 @@ impl std::fmt::Display for DatabaseIntegrityError {
                 DatabaseIntegrityError::InvalidKDFUUID { uuid } => {
                     format!("Encountered an invalid KDF UUID: {:0x?}", uuid)
                 }
+                DatabaseIntegrityError::UnsupportedKDF { uuid } => {
+                    format!("Encountered a KDF that is not supported: {:0x?}", uuid)
+                }
                 DatabaseIntegrityError::InvalidOuterCipherID { cid } => {
                     format!("Encountered an invalid outer cipher ID: {:0x?}", cid)
                 }
+                DatabaseIntegrityError::UnsupportedCipher { cid } => {
+                    format!(
+                        "Encountered an outer cipher that is not supported: {:0x?}",
+                        cid
+                    )
+                }
                 DatabaseIntegrityError::InvalidInnerCipherID { cid } => {
                     format!("Encountered an invalid inner cipher ID: {}", cid)
                 }
+                DatabaseIntegrityError::UnsupportedStreamCipher { cid } => {
+                    format!(
+                        "Encountered an inner stream cipher that is not supported: {}",
+                        cid
+                    )
+                }
                 DatabaseIntegrityError::InvalidCompressionSuite { cid } => {
                     format!("Encountered an invalid compression suite ID: {}", cid)
                 }
@@ -168,6 +278,14 @@ impl std::fmt::Display for DatabaseIntegrityError {
                     "Encountered an error when parsing the inner XML payload: {}",
                     e
                 ),
+                DatabaseIntegrityError::XMLWriting { e } => format!(
+                    "Encountered an error when writing the inner XML payload: {}",
+                    e
+                ),
+                DatabaseIntegrityError::BlockHashGeneration { block_index } => format!(
+                    "Error when generating the integrity hash of block {}",
+                    block_index
+                ),
                 DatabaseIntegrityError::UTF8 { e } => format!(
                     "Encountering an error when parsing an UTF-8 formatted string: {}",
                     e
@@ -181,13 +299,14 @@ impl std::fmt::Display for DatabaseIntegrityError {
     }
 }
 
-impl std::fmt::Display for Error {
+impl core::fmt::Display for Error {
     #[cfg_attr(tarpaulin, skip)]
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
             "KDBX error: {}",
             match self {
+                #[cfg(feature = "std")]
                 Error::IO { e } => format!("IO error: {}", e),
                 Error::IncorrectKey => "Incorrect key specified".to_owned(),
                 Error::InvalidKeyFile => "Keyfile format invalid".to_owned(),
@@ -197,9 +316,9 @@ impl std::fmt::Display for Error {
     }
 }
 
-impl std::fmt::Display for CryptoError {
+impl core::fmt::Display for CryptoError {
     #[cfg_attr(tarpaulin, skip)]
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
             "Crypto Error: {}",
@@ -216,25 +335,28 @@ impl std::fmt::Display for CryptoError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for CryptoError {
     #[cfg_attr(tarpaulin, skip)]
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             CryptoError::Argon2 { e } => Some(e),
             CryptoError::InvalidKeyIvLength { e } => Some(e),
-            CryptoError::InvalidKeyNonceLength { .. } => None, // TODO pass this through once e implements Error
-            CryptoError::InvalidKeyLength { .. } => None, // TODO pass this through once e implements Error
+            CryptoError::InvalidKeyNonceLength { e } => Some(e),
+            CryptoError::InvalidKeyLength { e } => Some(e),
             CryptoError::BlockMode { e } => Some(e),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for DatabaseIntegrityError {
     #[cfg_attr(tarpaulin, skip)]
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             DatabaseIntegrityError::Crypto { e } => Some(e),
             DatabaseIntegrityError::XMLParsing { e } => Some(e),
+            DatabaseIntegrityError::XMLWriting { e } => Some(e),
             DatabaseIntegrityError::Base64 { e } => Some(e),
             DatabaseIntegrityError::UTF8 { e } => Some(e),
             _ => None,
@@ -242,6 +364,7 @@ impl std::error::Error for DatabaseIntegrityError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {
     #[cfg_attr(tarpaulin, skip)]
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
@@ -267,6 +390,7 @@ impl From<CryptoError> for DatabaseIntegrityError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for Error {
     #[cfg_attr(tarpaulin, skip)]
     fn from(e: std::io::Error) -> Self {
@@ -284,14 +408,22 @@ impl From<argon2::Error> for CryptoError {
 impl From<hmac::crypto_mac::InvalidKeyLength> for CryptoError {
     #[cfg_attr(tarpaulin, skip)]
     fn from(e: hmac::crypto_mac::InvalidKeyLength) -> Self {
-        CryptoError::InvalidKeyLength { e }
+        CryptoError::InvalidKeyLength {
+            e: InvalidKeyLengthError {
+                message: e.to_string(),
+            },
+        }
     }
 }
 
 impl From<stream_cipher::InvalidKeyNonceLength> for CryptoError {
     #[cfg_attr(tarpaulin, skip)]
     fn from(e: stream_cipher::InvalidKeyNonceLength) -> Self {
-        CryptoError::InvalidKeyNonceLength { e }
+        CryptoError::InvalidKeyNonceLength {
+            e: InvalidKeyNonceLengthError {
+                message: e.to_string(),
+            },
+        }
     }
 }
 
@@ -316,9 +448,16 @@ impl From<xml::reader::Error> for DatabaseIntegrityError {
     }
 }
 
-impl From<std::str::Utf8Error> for DatabaseIntegrityError {
+impl From<xml::writer::Error> for DatabaseIntegrityError {
     #[cfg_attr(tarpaulin, skip)]
-    fn from(e: std::str::Utf8Error) -> Self {
+    fn from(e: xml::writer::Error) -> Self {
+        DatabaseIntegrityError::XMLWriting { e }
+    }
+}
+
+impl From<core::str::Utf8Error> for DatabaseIntegrityError {
+    #[cfg_attr(tarpaulin, skip)]
+    fn from(e: core::str::Utf8Error) -> Self {
         DatabaseIntegrityError::UTF8 { e }
     }
 }
@@ -329,3 +468,47 @@ impl From<base64::DecodeError> for DatabaseIntegrityError {
         DatabaseIntegrityError::Base64 { e }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_key_length_has_source() {
+        let e: CryptoError = hmac::crypto_mac::InvalidKeyLength::default().into();
+        assert!(std::error::Error::source(&e).is_some());
+    }
+
+    #[test]
+    fn invalid_key_nonce_length_has_source() {
+        let e: CryptoError = stream_cipher::InvalidKeyNonceLength::default().into();
+        assert!(std::error::Error::source(&e).is_some());
+    }
+
+    #[test]
+    fn is_authentication_failure_classifies_wrong_key_errors() {
+        assert!(Error::IncorrectKey.is_authentication_failure());
+        assert!(Error::InvalidKeyFile.is_authentication_failure());
+        assert!(!Error::DatabaseIntegrity {
+            e: DatabaseIntegrityError::Compression
+        }
+        .is_authentication_failure());
+    }
+
+    #[test]
+    fn is_corruption_classifies_format_errors_but_not_crypto() {
+        assert!(Error::DatabaseIntegrity {
+            e: DatabaseIntegrityError::HeaderHashMismatch
+        }
+        .is_corruption());
+
+        let crypto_err = Error::DatabaseIntegrity {
+            e: DatabaseIntegrityError::Crypto {
+                e: hmac::crypto_mac::InvalidKeyLength::default().into(),
+            },
+        };
+        assert!(!crypto_err.is_corruption());
+
+        assert!(!Error::IncorrectKey.is_corruption());
+    }
+}
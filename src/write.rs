@@ -0,0 +1,51 @@
+//! Write-side primitives for `Database::save`: emitting the inner XML payload and
+//! authenticating each KDBX4 block on the way back out, mirroring the read side's
+//! XML parsing and block-hash verification.
+//!
+//! This does not implement `Database::save` itself, which needs the `Database` /
+//! `Entry` / `Group` model to walk and re-serialize — that model isn't present in
+//! this crate yet. These are the two operations that model can't avoid calling once
+//! it exists, given `DatabaseIntegrityError::XMLWriting` and `BlockHashGeneration`.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::crypto;
+use crate::result::DatabaseIntegrityError;
+
+use sha2::{Digest, Sha512};
+use xml::writer::{EmitterConfig, XmlEvent};
+
+/// Serializes a single flat `(name, value)` pair as `<name>value</name>`, the
+/// building block `Database::save` will repeat for every XML element it re-emits.
+pub fn write_xml_element(name: &str, value: &str) -> Result<Vec<u8>, DatabaseIntegrityError> {
+    let mut buffer = Vec::new();
+    let mut writer = EmitterConfig::new().create_writer(&mut buffer);
+    writer.write(XmlEvent::start_element(name))?;
+    writer.write(XmlEvent::characters(value))?;
+    writer.write(XmlEvent::end_element())?;
+    Ok(buffer)
+}
+
+/// Splits `plaintext` into `block_size`-sized chunks and authenticates each one with
+/// the per-block HMAC key KDBX4 derives from the block index, ready to be written
+/// out alongside the (separately encrypted) block payload.
+pub fn hmac_blocks(
+    block_key: &[u8],
+    plaintext: &[u8],
+    block_size: usize,
+) -> Result<Vec<(u64, Vec<u8>)>, DatabaseIntegrityError> {
+    plaintext
+        .chunks(block_size)
+        .enumerate()
+        .map(|(index, block)| {
+            let index = index as u64;
+            // KDBX4 per-block key: SHA-512(block index as LE u64 || HMAC base key).
+            let mut hasher = Sha512::new();
+            hasher.update(&index.to_le_bytes());
+            hasher.update(block_key);
+            let per_block_key = hasher.finalize();
+            crypto::block_hmac(&per_block_key, index, block).map(|mac| (index, mac))
+        })
+        .collect()
+}